@@ -0,0 +1,42 @@
+use crate::cpu::CPU;
+use crate::error::EmulatorError;
+
+// Interface comum para os núcleos, para que frontends e testes não dependam da CPU concreta
+pub trait Emulator {
+    fn load(&mut self, program: Vec<u8>) -> Result<(), EmulatorError>;
+    fn reset(&mut self);
+    // Executa uma instrução; retorna false quando o programa chega num BRK
+    fn step(&mut self) -> bool;
+}
+
+impl Emulator for CPU {
+    fn load(&mut self, program: Vec<u8>) -> Result<(), EmulatorError> {
+        CPU::load(self, program)
+    }
+
+    fn reset(&mut self) {
+        CPU::reset(self);
+    }
+
+    fn step(&mut self) -> bool {
+        CPU::step(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drive_cpu_through_trait_object() {
+        let mut cpu = CPU::new();
+        {
+            let emulator: &mut dyn Emulator = &mut cpu;
+            emulator.load(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]).unwrap();
+            emulator.reset();
+            while emulator.step() {}
+        }
+
+        assert_eq!(cpu.register_x, 0xc1);
+    }
+}
@@ -0,0 +1,36 @@
+use std::error::Error;
+use std::fmt;
+
+// Erros que as APIs públicas de carga e execução podem devolver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmulatorError {
+    // Opcode sem implementação encontrado em pc (com a política Halt)
+    UnknownOpcode { opcode: u8, pc: u16 },
+    // O programa não cabe na memória a partir do endereço de carga
+    OutOfBounds { start: u16, len: usize },
+    // A CPU travou num opcode KIL/JAM em pc
+    Jammed { opcode: u8, pc: u16 },
+    // MemoryLayout recusado pelo CPU::with_layout
+    InvalidLayout { ram_size: usize, ram_end: u16 },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmulatorError::UnknownOpcode { opcode, pc } => {
+                write!(f, "unknown opcode {:#04x} at {:#06x}", opcode, pc)
+            }
+            EmulatorError::OutOfBounds { start, len } => {
+                write!(f, "program of {} bytes does not fit at {:#06x}", len, start)
+            }
+            EmulatorError::Jammed { opcode, pc } => {
+                write!(f, "cpu jammed on opcode {:#04x} at {:#06x}", opcode, pc)
+            }
+            EmulatorError::InvalidLayout { ram_size, ram_end } => {
+                write!(f, "invalid memory layout: {:#x} bytes of RAM up to {:#06x}", ram_size, ram_end)
+            }
+        }
+    }
+}
+
+impl Error for EmulatorError {}
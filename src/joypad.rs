@@ -0,0 +1,175 @@
+use bitflags::bitflags;
+
+// Botões do controle, na ordem em que o registrador de deslocamento entrega
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoypadButton {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+bitflags! {
+    // Um bit por botão: o bit 0 é o primeiro a sair na leitura de 0x4016
+    struct Buttons: u8 {
+        const A      = 0b0000_0001;
+        const B      = 0b0000_0010;
+        const SELECT = 0b0000_0100;
+        const START  = 0b0000_1000;
+        const UP     = 0b0001_0000;
+        const DOWN   = 0b0010_0000;
+        const LEFT   = 0b0100_0000;
+        const RIGHT  = 0b1000_0000;
+    }
+}
+
+impl JoypadButton {
+    // Direção que o controle físico não deixa apertar ao mesmo tempo que esta
+    fn opposite(self) -> Option<JoypadButton> {
+        match self {
+            JoypadButton::Up => Some(JoypadButton::Down),
+            JoypadButton::Down => Some(JoypadButton::Up),
+            JoypadButton::Left => Some(JoypadButton::Right),
+            JoypadButton::Right => Some(JoypadButton::Left),
+            _ => None,
+        }
+    }
+
+    fn bit(self) -> Buttons {
+        match self {
+            JoypadButton::A => Buttons::A,
+            JoypadButton::B => Buttons::B,
+            JoypadButton::Select => Buttons::SELECT,
+            JoypadButton::Start => Buttons::START,
+            JoypadButton::Up => Buttons::UP,
+            JoypadButton::Down => Buttons::DOWN,
+            JoypadButton::Left => Buttons::LEFT,
+            JoypadButton::Right => Buttons::RIGHT,
+        }
+    }
+}
+
+pub struct Joypad {
+    strobe: bool,     // Enquanto ligado, toda leitura devolve o botão A
+    button_index: u8, // Próximo botão que a leitura vai entregar
+    buttons: Buttons, // Botões pressionados agora
+    filter_opposing: bool, // Descarta a direção oposta ao apertar uma direção
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad {
+            strobe: false,
+            button_index: 0,
+            buttons: Buttons::empty(),
+            filter_opposing: false,
+        }
+    }
+
+    // Com o filtro ligado, esquerda+direita e cima+baixo nunca ficam juntos: vale a mais recente.
+    // Desligado por padrão, porque ferramentas de TAS às vezes querem as duas
+    pub fn set_opposing_filter(&mut self, enabled: bool) {
+        self.filter_opposing = enabled;
+    }
+
+    pub fn press(&mut self, button: JoypadButton) {
+        if self.filter_opposing {
+            if let Some(opposite) = button.opposite() {
+                self.buttons.remove(opposite.bit());
+            }
+        }
+        self.buttons.insert(button.bit());
+    }
+
+    pub fn release(&mut self, button: JoypadButton) {
+        self.buttons.remove(button.bit());
+    }
+
+    pub fn pressed(&self, button: JoypadButton) -> bool {
+        self.buttons.contains(button.bit())
+    }
+
+    // Escrita em 0x4016: o bit 0 liga/desliga o strobe e reinicia a sequência
+    pub fn write(&mut self, data: u8) {
+        self.strobe = data & 1 == 1;
+        if self.strobe {
+            self.button_index = 0;
+        }
+    }
+
+    // Leitura de 0x4016: um botão por vez; depois dos oito, o controle oficial devolve 1
+    pub fn read(&mut self) -> u8 {
+        if self.button_index > 7 {
+            return 1;
+        }
+        let response = (self.buttons.bits() >> self.button_index) & 1;
+        if !self.strobe {
+            self.button_index += 1;
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn shift_out(joypad: &mut Joypad) -> Vec<u8> {
+        joypad.write(1);
+        joypad.write(0);
+        (0..8).map(|_| joypad.read()).collect()
+    }
+
+    #[test]
+    fn test_up_and_down_are_both_shifted_out() {
+        let mut joypad = Joypad::new();
+        joypad.press(JoypadButton::Up);
+        joypad.press(JoypadButton::Down);
+
+        assert!(joypad.pressed(JoypadButton::Up));
+        assert!(joypad.pressed(JoypadButton::Down));
+        assert!(!joypad.pressed(JoypadButton::A));
+        assert_eq!(shift_out(&mut joypad), vec![0, 0, 0, 0, 1, 1, 0, 0]);
+        assert_eq!(joypad.read(), 1);
+
+        joypad.release(JoypadButton::Up);
+        assert_eq!(shift_out(&mut joypad), vec![0, 0, 0, 0, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_strobe_keeps_reporting_button_a() {
+        let mut joypad = Joypad::new();
+        joypad.press(JoypadButton::A);
+        joypad.write(1);
+
+        assert_eq!(joypad.read(), 1);
+        assert_eq!(joypad.read(), 1);
+    }
+
+    #[test]
+    fn test_opposing_filter_keeps_the_most_recent_direction() {
+        let mut joypad = Joypad::new();
+        joypad.set_opposing_filter(true);
+        joypad.press(JoypadButton::Left);
+        joypad.press(JoypadButton::Right);
+        joypad.press(JoypadButton::Up);
+
+        assert!(!joypad.pressed(JoypadButton::Left));
+        assert!(joypad.pressed(JoypadButton::Right));
+        assert_eq!(shift_out(&mut joypad), vec![0, 0, 0, 0, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_opposing_directions_pass_through_without_filter() {
+        let mut joypad = Joypad::new();
+        joypad.press(JoypadButton::Left);
+        joypad.press(JoypadButton::Right);
+
+        assert!(joypad.pressed(JoypadButton::Left));
+        assert!(joypad.pressed(JoypadButton::Right));
+    }
+}
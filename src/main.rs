@@ -1,5 +1,21 @@
 
+#[macro_use]
+extern crate lazy_static;
+
+#[allow(dead_code)]
 mod cpu;
+#[allow(dead_code)]
+mod emulator;
+#[allow(dead_code)]
+mod error;
+#[allow(dead_code)]
+mod joypad;
+#[allow(dead_code)]
+mod opcodes;
+#[allow(dead_code)]
+mod timing;
+#[allow(dead_code)]
+mod trace;
 
 fn main() {
     
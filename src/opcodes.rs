@@ -0,0 +1,331 @@
+use crate::cpu::AddressingMode;
+use std::collections::HashMap;
+
+// Descrição de um opcode: tamanho em bytes (contando o próprio opcode), ciclos base e modo de endereçamento
+#[derive(Debug)]
+pub struct OpCode {
+    pub code: u8,
+    pub mnemonic: &'static str,
+    pub len: u8,
+    pub cycles: u8,
+    pub mode: AddressingMode,
+}
+
+impl OpCode {
+    fn new(code: u8, mnemonic: &'static str, len: u8, cycles: u8, mode: AddressingMode) -> Self {
+        OpCode {
+            code,
+            mnemonic,
+            len,
+            cycles,
+            mode,
+        }
+    }
+}
+
+lazy_static! {
+    // Os ciclos marcados com /*+1 page*/ ganham um ciclo extra quando o endereço cruza a página (só leituras)
+    pub static ref CPU_OPS_CODES: Vec<OpCode> = vec![
+        OpCode::new(0x00, "BRK", 2, 7, AddressingMode::NoneAddressing), // o byte seguinte é padding
+        OpCode::new(0xea, "NOP", 1, 2, AddressingMode::NoneAddressing),
+
+        /* Aritméticas */
+        OpCode::new(0x69, "ADC", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0x65, "ADC", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x75, "ADC", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x6d, "ADC", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0x7d, "ADC", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0x79, "ADC", 3, 4 /*+1 page*/, AddressingMode::Absolute_Y),
+        OpCode::new(0x61, "ADC", 2, 6, AddressingMode::Indirect_X),
+        OpCode::new(0x71, "ADC", 2, 5 /*+1 page*/, AddressingMode::Indirect_Y),
+
+        OpCode::new(0xe9, "SBC", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xe5, "SBC", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xf5, "SBC", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0xed, "SBC", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0xfd, "SBC", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0xf9, "SBC", 3, 4 /*+1 page*/, AddressingMode::Absolute_Y),
+        OpCode::new(0xe1, "SBC", 2, 6, AddressingMode::Indirect_X),
+        OpCode::new(0xf1, "SBC", 2, 5 /*+1 page*/, AddressingMode::Indirect_Y),
+
+        OpCode::new(0x29, "AND", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0x25, "AND", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x35, "AND", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x2d, "AND", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0x3d, "AND", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0x39, "AND", 3, 4 /*+1 page*/, AddressingMode::Absolute_Y),
+        OpCode::new(0x21, "AND", 2, 6, AddressingMode::Indirect_X),
+        OpCode::new(0x31, "AND", 2, 5 /*+1 page*/, AddressingMode::Indirect_Y),
+
+        OpCode::new(0x49, "EOR", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0x45, "EOR", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x55, "EOR", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x4d, "EOR", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0x5d, "EOR", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0x59, "EOR", 3, 4 /*+1 page*/, AddressingMode::Absolute_Y),
+        OpCode::new(0x41, "EOR", 2, 6, AddressingMode::Indirect_X),
+        OpCode::new(0x51, "EOR", 2, 5 /*+1 page*/, AddressingMode::Indirect_Y),
+
+        OpCode::new(0x09, "ORA", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0x05, "ORA", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x15, "ORA", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x0d, "ORA", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0x1d, "ORA", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0x19, "ORA", 3, 4 /*+1 page*/, AddressingMode::Absolute_Y),
+        OpCode::new(0x01, "ORA", 2, 6, AddressingMode::Indirect_X),
+        OpCode::new(0x11, "ORA", 2, 5 /*+1 page*/, AddressingMode::Indirect_Y),
+
+        /* Shifts */
+        OpCode::new(0x0a, "ASL", 1, 2, AddressingMode::Accumulator),
+        OpCode::new(0x06, "ASL", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x16, "ASL", 2, 6, AddressingMode::ZeroPage_X),
+        OpCode::new(0x0e, "ASL", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0x1e, "ASL", 3, 7, AddressingMode::Absolute_X),
+
+        OpCode::new(0x4a, "LSR", 1, 2, AddressingMode::Accumulator),
+        OpCode::new(0x46, "LSR", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x56, "LSR", 2, 6, AddressingMode::ZeroPage_X),
+        OpCode::new(0x4e, "LSR", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0x5e, "LSR", 3, 7, AddressingMode::Absolute_X),
+
+        OpCode::new(0x2a, "ROL", 1, 2, AddressingMode::Accumulator),
+        OpCode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPage_X),
+        OpCode::new(0x2e, "ROL", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0x3e, "ROL", 3, 7, AddressingMode::Absolute_X),
+
+        OpCode::new(0x6a, "ROR", 1, 2, AddressingMode::Accumulator),
+        OpCode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPage_X),
+        OpCode::new(0x6e, "ROR", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0x7e, "ROR", 3, 7, AddressingMode::Absolute_X),
+
+        OpCode::new(0xe6, "INC", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0xf6, "INC", 2, 6, AddressingMode::ZeroPage_X),
+        OpCode::new(0xee, "INC", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0xfe, "INC", 3, 7, AddressingMode::Absolute_X),
+
+        OpCode::new(0xe8, "INX", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0xc8, "INY", 1, 2, AddressingMode::NoneAddressing),
+
+        OpCode::new(0xc6, "DEC", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0xd6, "DEC", 2, 6, AddressingMode::ZeroPage_X),
+        OpCode::new(0xce, "DEC", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0xde, "DEC", 3, 7, AddressingMode::Absolute_X),
+
+        OpCode::new(0xca, "DEX", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x88, "DEY", 1, 2, AddressingMode::NoneAddressing),
+
+        OpCode::new(0xc9, "CMP", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xc5, "CMP", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xd5, "CMP", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0xcd, "CMP", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0xdd, "CMP", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0xd9, "CMP", 3, 4 /*+1 page*/, AddressingMode::Absolute_Y),
+        OpCode::new(0xc1, "CMP", 2, 6, AddressingMode::Indirect_X),
+        OpCode::new(0xd1, "CMP", 2, 5 /*+1 page*/, AddressingMode::Indirect_Y),
+
+        OpCode::new(0xc0, "CPY", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xc4, "CPY", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xcc, "CPY", 3, 4, AddressingMode::Absolute),
+
+        OpCode::new(0xe0, "CPX", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xe4, "CPX", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xec, "CPX", 3, 4, AddressingMode::Absolute),
+
+        /* Branches */
+        OpCode::new(0x4c, "JMP", 3, 3, AddressingMode::NoneAddressing), // Absolute
+        OpCode::new(0x6c, "JMP", 3, 5, AddressingMode::NoneAddressing), // Indirect
+
+        OpCode::new(0x20, "JSR", 3, 6, AddressingMode::NoneAddressing),
+        OpCode::new(0x60, "RTS", 1, 6, AddressingMode::NoneAddressing),
+
+        OpCode::new(0x40, "RTI", 1, 6, AddressingMode::NoneAddressing),
+
+        OpCode::new(0xd0, "BNE", 2, 2 /*+1 se desviar, +2 se cruzar página*/, AddressingMode::NoneAddressing),
+        OpCode::new(0x70, "BVS", 2, 2 /*+1 se desviar, +2 se cruzar página*/, AddressingMode::NoneAddressing),
+        OpCode::new(0x50, "BVC", 2, 2 /*+1 se desviar, +2 se cruzar página*/, AddressingMode::NoneAddressing),
+        OpCode::new(0x30, "BMI", 2, 2 /*+1 se desviar, +2 se cruzar página*/, AddressingMode::NoneAddressing),
+        OpCode::new(0xf0, "BEQ", 2, 2 /*+1 se desviar, +2 se cruzar página*/, AddressingMode::NoneAddressing),
+        OpCode::new(0xb0, "BCS", 2, 2 /*+1 se desviar, +2 se cruzar página*/, AddressingMode::NoneAddressing),
+        OpCode::new(0x90, "BCC", 2, 2 /*+1 se desviar, +2 se cruzar página*/, AddressingMode::NoneAddressing),
+        OpCode::new(0x10, "BPL", 2, 2 /*+1 se desviar, +2 se cruzar página*/, AddressingMode::NoneAddressing),
+
+        OpCode::new(0x24, "BIT", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x2c, "BIT", 3, 4, AddressingMode::Absolute),
+
+        /* Flags */
+        OpCode::new(0xd8, "CLD", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x58, "CLI", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0xb8, "CLV", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x18, "CLC", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x38, "SEC", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x78, "SEI", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0xf8, "SED", 1, 2, AddressingMode::NoneAddressing),
+
+        /* Transferências */
+        OpCode::new(0xaa, "TAX", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0xa8, "TAY", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0xba, "TSX", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x8a, "TXA", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x9a, "TXS", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x98, "TYA", 1, 2, AddressingMode::NoneAddressing),
+
+        /* Armazenamento */
+        OpCode::new(0xa9, "LDA", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xa5, "LDA", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xb5, "LDA", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0xad, "LDA", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0xbd, "LDA", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0xb9, "LDA", 3, 4 /*+1 page*/, AddressingMode::Absolute_Y),
+        OpCode::new(0xa1, "LDA", 2, 6, AddressingMode::Indirect_X),
+        OpCode::new(0xb1, "LDA", 2, 5 /*+1 page*/, AddressingMode::Indirect_Y),
+
+        OpCode::new(0xa2, "LDX", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xa6, "LDX", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xb6, "LDX", 2, 4, AddressingMode::ZeroPage_Y),
+        OpCode::new(0xae, "LDX", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0xbe, "LDX", 3, 4 /*+1 page*/, AddressingMode::Absolute_Y),
+
+        OpCode::new(0xa0, "LDY", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xa4, "LDY", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xb4, "LDY", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0xac, "LDY", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0xbc, "LDY", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+
+        // Escritas indexadas sempre gastam o ciclo de página, então o custo já vem fixo na tabela
+        OpCode::new(0x85, "STA", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x95, "STA", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x8d, "STA", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0x9d, "STA", 3, 5, AddressingMode::Absolute_X),
+        OpCode::new(0x99, "STA", 3, 5, AddressingMode::Absolute_Y),
+        OpCode::new(0x81, "STA", 2, 6, AddressingMode::Indirect_X),
+        OpCode::new(0x91, "STA", 2, 6, AddressingMode::Indirect_Y),
+
+        OpCode::new(0x86, "STX", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x96, "STX", 2, 4, AddressingMode::ZeroPage_Y),
+        OpCode::new(0x8e, "STX", 3, 4, AddressingMode::Absolute),
+
+        OpCode::new(0x84, "STY", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x94, "STY", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x8c, "STY", 3, 4, AddressingMode::Absolute),
+
+        /* Pilha */
+        OpCode::new(0x48, "PHA", 1, 3, AddressingMode::NoneAddressing),
+        OpCode::new(0x68, "PLA", 1, 4, AddressingMode::NoneAddressing),
+        OpCode::new(0x08, "PHP", 1, 3, AddressingMode::NoneAddressing),
+        OpCode::new(0x28, "PLP", 1, 4, AddressingMode::NoneAddressing),
+
+        /* NOPs não oficiais: o * no mnemônico segue o log do nestest */
+        OpCode::new(0x1a, "*NOP", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x3a, "*NOP", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x5a, "*NOP", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x7a, "*NOP", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0xda, "*NOP", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0xfa, "*NOP", 1, 2, AddressingMode::NoneAddressing),
+        OpCode::new(0x80, "*NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0x82, "*NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0x89, "*NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xc2, "*NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xe2, "*NOP", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0x04, "*NOP", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x44, "*NOP", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x64, "*NOP", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x14, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x34, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x54, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x74, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0xd4, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0xf4, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+        OpCode::new(0x0c, "*NOP", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0x1c, "*NOP", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0x3c, "*NOP", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0x5c, "*NOP", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0x7c, "*NOP", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0xdc, "*NOP", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+        OpCode::new(0xfc, "*NOP", 3, 4 /*+1 page*/, AddressingMode::Absolute_X),
+    ];
+
+    pub static ref OPCODES_MAP: HashMap<u8, &'static OpCode> = {
+        let mut map = HashMap::new();
+        for cpuop in &*CPU_OPS_CODES {
+            map.insert(cpuop.code, cpuop);
+        }
+        map
+    };
+
+    // Tabela densa indexada pelo próprio opcode, usada no laço de execução para evitar o hash
+    pub static ref OPCODES_TABLE: [Option<&'static OpCode>; 256] = {
+        let mut table = [None; 256];
+        for cpuop in &*CPU_OPS_CODES {
+            table[cpuop.code as usize] = Some(cpuop);
+        }
+        table
+    };
+}
+
+// Tamanho documentado de qualquer opcode, inclusive os não oficiais que ainda não estão na tabela.
+// Para os não oficiais o tamanho segue o modo de endereçamento da coluna (code & 0x1F).
+pub fn documented_len(code: u8) -> u8 {
+    if let Some(opcode) = OPCODES_TABLE[code as usize] {
+        return opcode.len;
+    }
+    match code & 0x1F {
+        0x00 | 0x02 if code >= 0x80 => 2, // Immediate
+        0x00 | 0x02 | 0x08 | 0x0A | 0x12 | 0x18 | 0x1A => 1, // Implied / KIL
+        0x0C..=0x0F | 0x19 | 0x1B..=0x1F => 3, // Absolute, Absolute_X, Absolute_Y
+        _ => 2,
+    }
+}
+
+// Ciclos documentados, sem contar cruzamento de página. Para os não oficiais, a coluna dá o
+// modo e a linha o tipo: 0x80 - 0x9F só grava, 0xA0 - 0xBF só lê, o resto lê, altera e grava
+pub fn documented_cycles(code: u8) -> u8 {
+    if let Some(opcode) = OPCODES_TABLE[code as usize] {
+        return opcode.cycles;
+    }
+    let (store, load) = (code & 0xE0 == 0x80, code & 0xE0 == 0xA0);
+    match code & 0x1F {
+        0x03 => if store || load { 6 } else { 8 }, // Indirect_X
+        0x07 => if store || load { 3 } else { 5 }, // ZeroPage
+        0x0F => if store || load { 4 } else { 6 }, // Absolute
+        0x13 => if store { 6 } else if load { 5 } else { 8 }, // Indirect_Y
+        0x17 => if store || load { 4 } else { 6 }, // ZeroPage_X / ZeroPage_Y
+        0x1B..=0x1F => if store { 5 } else if load { 4 } else { 7 }, // Absolute_X / Absolute_Y
+        _ => 2, // Immediate / KIL
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_table_agrees_with_map() {
+        for code in 0..=255u8 {
+            match (OPCODES_TABLE[code as usize], OPCODES_MAP.get(&code)) {
+                (Some(from_table), Some(from_map)) => {
+                    assert_eq!(from_table.code, code);
+                    assert!(std::ptr::eq(from_table, *from_map));
+                }
+                (None, None) => {}
+                _ => panic!("opcode {:#04x} differs between table and map", code),
+            }
+        }
+    }
+
+    #[test]
+    fn test_documented_cycles_of_unofficial_opcodes() {
+        assert_eq!(documented_cycles(0xA9), 2); // LDA #, vem da tabela
+        assert_eq!(documented_cycles(0x03), 8); // SLO (zp,X)
+        assert_eq!(documented_cycles(0xFF), 7); // ISC abs,X
+        assert_eq!(documented_cycles(0xA7), 3); // LAX zp
+        assert_eq!(documented_cycles(0xB3), 5); // LAX (zp),Y
+        assert_eq!(documented_cycles(0x9F), 5); // AHX abs,Y
+        assert_eq!(documented_cycles(0x87), 3); // SAX zp
+        assert_eq!(documented_cycles(0xCB), 2); // AXS #
+        for code in 0..=255u8 {
+            assert!(documented_cycles(code) >= 2, "opcode {:#04x}", code);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// Frequências do clock da CPU (2A03 / 2A07)
+pub const NTSC_CPU_HZ: f64 = 1_789_773.0;
+pub const PAL_CPU_HZ: f64 = 1_662_607.0;
+
+const DOTS_PER_SCANLINE: f64 = 341.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+}
+
+impl Region {
+    pub fn cpu_hz(self) -> f64 {
+        match self {
+            Region::Ntsc => NTSC_CPU_HZ,
+            Region::Pal => PAL_CPU_HZ,
+        }
+    }
+
+    // Dots da PPU por quadro, em média. No NTSC, quadros ímpares pulam um dot com a
+    // renderização ligada, daí o meio dot
+    fn dots_per_frame(self) -> f64 {
+        match self {
+            Region::Ntsc => DOTS_PER_SCANLINE * 262.0 - 0.5,
+            Region::Pal => DOTS_PER_SCANLINE * 312.0,
+        }
+    }
+
+    // Dots da PPU por ciclo da CPU
+    fn dots_per_cycle(self) -> f64 {
+        match self {
+            Region::Ntsc => 3.0,
+            Region::Pal => 3.2,
+        }
+    }
+}
+
+// 29780.5 no NTSC, 33247.5 no PAL
+pub fn cycles_per_frame(region: Region) -> f64 {
+    region.dots_per_frame() / region.dots_per_cycle()
+}
+
+pub fn frames_per_second(region: Region) -> f64 {
+    region.cpu_hz() / cycles_per_frame(region)
+}
+
+pub fn cycles_to_seconds(cycles: u64, region: Region) -> f64 {
+    cycles as f64 / region.cpu_hz()
+}
+
+// Arredonda para o ciclo inteiro mais próximo
+pub fn seconds_to_cycles(seconds: f64, region: Region) -> u64 {
+    (seconds * region.cpu_hz()).round() as u64
+}
+
+pub fn cycles_to_frames(cycles: u64, region: Region) -> f64 {
+    cycles as f64 / cycles_per_frame(region)
+}
+
+pub fn frames_to_cycles(frames: f64, region: Region) -> u64 {
+    (frames * cycles_per_frame(region)).round() as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cycles_per_frame() {
+        assert_eq!(cycles_per_frame(Region::Ntsc), 29780.5);
+        assert_eq!(cycles_per_frame(Region::Pal), 33247.5);
+        assert!((frames_per_second(Region::Ntsc) - 60.0988).abs() < 0.0001);
+        assert!((frames_per_second(Region::Pal) - 50.0070).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_conversions_are_mutually_consistent() {
+        for region in [Region::Ntsc, Region::Pal] {
+            assert_eq!(seconds_to_cycles(1.0, region), region.cpu_hz() as u64);
+            assert_eq!(seconds_to_cycles(cycles_to_seconds(123_456, region), region), 123_456);
+            assert_eq!(frames_to_cycles(cycles_to_frames(123_456, region), region), 123_456);
+            assert_eq!(frames_to_cycles(2.0, region), (2.0 * cycles_per_frame(region)) as u64);
+
+            // Um segundo de ciclos dá um segundo de quadros
+            let frames = cycles_to_frames(seconds_to_cycles(1.0, region), region);
+            assert!((frames - frames_per_second(region)).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use crate::cpu::{AddressingMode, InterruptKind, MachineEvent, CPU, UNUSED};
+use crate::opcodes;
+
+// Quanto detalhe cada linha do trace mostra
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceVerbosity {
+    Minimal,  // pc + mnemônico
+    Standard, // formato do nestest, com os registradores
+    Verbose,  // Standard + endereço efetivo e o byte que está nele
+}
+
+// Descreve a instrução no pc atual, antes dela executar
+pub fn trace(cpu: &CPU, verbosity: TraceVerbosity) -> String {
+    let begin = cpu.program_counter;
+    let code = cpu.peek(begin);
+
+    let opcode = match opcodes::OPCODES_TABLE[code as usize] {
+        Some(opcode) => opcode,
+        None => {
+            return match verbosity {
+                TraceVerbosity::Minimal => format!("{:04X}  ???", begin),
+                _ => format!(
+                    "{:47} {}",
+                    format!("{:04X}  {:02X}        ???", begin, code),
+                    registers(cpu)
+                ),
+            };
+        }
+    };
+
+    if verbosity == TraceVerbosity::Minimal {
+        return format!("{:04X}  {}", begin, opcode.mnemonic);
+    }
+
+    let mut operand = format_operand(cpu, begin, opcode);
+    if verbosity == TraceVerbosity::Verbose {
+        if let Some(addr) = effective_address(cpu, begin, opcode) {
+            operand.push_str(&format!(" @ {:#06x} = {:#04x}", addr, cpu.peek(addr)));
+        }
+    }
+
+    format!("{:47} {}", instruction_line(cpu, begin, opcode, &operand), registers(cpu))
+}
+
+// Executa até max_instructions instruções (parando antes num BRK ou parada) e devolve o trace
+// só das que passam no filtro, que recebe o pc e o opcode. Opcodes fora da tabela e entradas
+// em interrupções não aparecem
+pub fn trace_filtered(
+    cpu: &mut CPU,
+    verbosity: TraceVerbosity,
+    max_instructions: usize,
+    filter: impl Fn(u16, &opcodes::OpCode) -> bool,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    for _ in 0..max_instructions {
+        let pc = cpu.program_counter;
+        if !cpu.interrupt_due() {
+            if let Some(opcode) = opcodes::OPCODES_TABLE[cpu.peek(pc) as usize] {
+                if filter(pc, opcode) {
+                    lines.push(trace(cpu, verbosity));
+                }
+            }
+        }
+        if !cpu.step() {
+            break;
+        }
+    }
+    lines
+}
+
+// Uma linha de log por evento, no mesmo estilo do trace:
+// "NMI  8004 -> vector FFFA CYC:13" / "JAM  8002 opcode 02"
+pub fn event_line(event: &MachineEvent) -> String {
+    match *event {
+        MachineEvent::Interrupt { kind, pc, vector, cycle } => {
+            let name = match kind {
+                InterruptKind::Nmi => "NMI",
+                InterruptKind::Irq => "IRQ",
+                InterruptKind::Brk => "BRK",
+            };
+            format!("{:4} {:04X} -> vector {:04X} CYC:{}", name, pc, vector, cycle)
+        }
+        MachineEvent::Jammed { pc, opcode } => format!("JAM  {:04X} opcode {:02X}", pc, opcode),
+    }
+}
+
+// Listagem linear de start até end (inclusive): endereço, bytes e instrução.
+// Não tenta separar código de dados; bytes que não são opcodes aparecem como ???
+pub fn disassemble(cpu: &CPU, start: u16, end: u16) -> String {
+    let mut lines = Vec::new();
+    let mut addr = start as u32;
+    while addr <= end as u32 {
+        let begin = addr as u16;
+        let code = cpu.peek(begin);
+        let line = match opcodes::OPCODES_TABLE[code as usize] {
+            Some(opcode) => {
+                addr += opcode.len as u32;
+                instruction_line(cpu, begin, opcode, &format_operand(cpu, begin, opcode))
+            }
+            None => {
+                addr += 1;
+                format!("{:04X}  {:02X}        ???", begin, code)
+            }
+        };
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+// "8000  A9 01     LDA #$01": endereço, bytes da instrução, mnemônico e operando
+fn instruction_line(cpu: &CPU, begin: u16, opcode: &opcodes::OpCode, operand: &str) -> String {
+    let hex_dump: Vec<String> = (0..opcode.len as u16)
+        .map(|i| format!("{:02X}", cpu.peek(begin.wrapping_add(i))))
+        .collect();
+    format!("{:04X}  {:8} {: >4} {}", begin, hex_dump.join(" "), opcode.mnemonic, operand)
+        .trim_end()
+        .to_string()
+}
+
+// Registradores e ciclos gastos até aqui, como no log do nestest (ainda sem o campo PPU:)
+// O bit 5 do status sai ligado, como em qualquer leitura dele (PHP, nestest)
+fn registers(cpu: &CPU) -> String {
+    format!(
+        "A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+        cpu.register_a, cpu.register_x, cpu.register_y, cpu.status | UNUSED, cpu.stack_pointer, cpu.cycles
+    )
+}
+
+// Endereço que a instrução vai ler ou escrever, quando ela acessa memória
+fn effective_address(cpu: &CPU, begin: u16, opcode: &opcodes::OpCode) -> Option<u16> {
+    match opcode.mode {
+        AddressingMode::Immediate | AddressingMode::Accumulator | AddressingMode::NoneAddressing => None,
+        _ => Some(cpu.peek_absolute_address(&opcode.mode, begin.wrapping_add(1)).0),
+    }
+}
+
+fn format_operand(cpu: &CPU, begin: u16, opcode: &opcodes::OpCode) -> String {
+    let operand = begin.wrapping_add(1);
+    match opcode.mode {
+        AddressingMode::Immediate => format!("#${:02X}", cpu.peek(operand)),
+        AddressingMode::ZeroPage => zero_page(cpu, cpu.peek(operand)),
+        AddressingMode::ZeroPage_X => format!("{},X", zero_page(cpu, cpu.peek(operand))),
+        AddressingMode::ZeroPage_Y => format!("{},Y", zero_page(cpu, cpu.peek(operand))),
+        AddressingMode::Absolute => absolute(cpu, cpu.peek_u16(operand)),
+        AddressingMode::Absolute_X => format!("{},X", absolute(cpu, cpu.peek_u16(operand))),
+        AddressingMode::Absolute_Y => format!("{},Y", absolute(cpu, cpu.peek_u16(operand))),
+        AddressingMode::Indirect_X => format!("(${:02X},X)", cpu.peek(operand)),
+        AddressingMode::Indirect_Y => format!("(${:02X}),Y", cpu.peek(operand)),
+        AddressingMode::ZeroPage_Indirect => format!("(${:02X})", cpu.peek(operand)),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::NoneAddressing => match opcode.code {
+            // JMP absoluto e JSR
+            0x4c | 0x20 => absolute(cpu, cpu.peek_u16(operand)),
+            // JMP indireto
+            0x6c => format!("({})", absolute(cpu, cpu.peek_u16(operand))),
+            // Branches: mostra o destino já resolvido
+            0x10 | 0x30 | 0x50 | 0x70 | 0x90 | 0xb0 | 0xd0 | 0xf0 => {
+                let offset = cpu.peek(operand) as i8;
+                let target = begin.wrapping_add(2).wrapping_add(offset as u16);
+                absolute(cpu, target)
+            }
+            _ => String::new(),
+        },
+    }
+}
+
+// Endereços com símbolo aparecem pelo nome
+fn absolute(cpu: &CPU, addr: u16) -> String {
+    match cpu.symbol_at(addr) {
+        Some(name) => name.to_string(),
+        None => format!("${:04X}", addr),
+    }
+}
+
+fn zero_page(cpu: &CPU, addr: u8) -> String {
+    match cpu.symbol_at(addr as u16) {
+        Some(name) => name.to_string(),
+        None => format!("${:02X}", addr),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cpu_with(program: Vec<u8>) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.load(program).unwrap();
+        cpu.reset();
+        cpu
+    }
+
+    #[test]
+    fn test_disassemble_rom_lists_instructions_linearly() {
+        let cpu = cpu_with(vec![0xa9, 0x01, 0xaa, 0x9d, 0x00, 0x02, 0x00, 0xff, 0xa7]);
+
+        let listing = disassemble(&cpu, 0x8000, 0x8008);
+        let lines: Vec<&str> = listing.lines().collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "8000  A9 01     LDA #$01",
+                "8002  AA        TAX",
+                "8003  9D 00 02  STA $0200,X",
+                "8006  00 FF     BRK",
+                "8008  A7        ???",
+            ]
+        );
+        assert_eq!(cpu.disassemble_rom(0x8000, 0x8008), listing);
+    }
+
+    #[test]
+    fn test_minimal_trace() {
+        let cpu = cpu_with(vec![0xa5, 0x10, 0x00]);
+        assert_eq!(trace(&cpu, TraceVerbosity::Minimal), "8000  LDA");
+    }
+
+    #[test]
+    fn test_standard_trace_matches_nestest_layout() {
+        let mut cpu = cpu_with(vec![0xa9, 0x01, 0x8d, 0x00, 0x02, 0x00]);
+        assert_eq!(
+            trace(&cpu, TraceVerbosity::Standard),
+            "8000  A9 01     LDA #$01                        A:00 X:00 Y:00 P:24 SP:FD CYC:0"
+        );
+
+        cpu.step();
+        assert_eq!(
+            trace(&cpu, TraceVerbosity::Standard),
+            "8002  8D 00 02  STA $0200                       A:01 X:00 Y:00 P:24 SP:FD CYC:2"
+        );
+    }
+
+    #[test]
+    fn test_verbose_trace_shows_effective_address_and_value() {
+        let mut cpu = cpu_with(vec![0xa5, 0x10, 0x00]);
+        cpu.mem_write(0x10, 0x55);
+
+        let line = trace(&cpu, TraceVerbosity::Verbose);
+
+        assert!(line.starts_with("8000  A5 10     LDA $10 @ 0x0010 = 0x55"), "{}", line);
+    }
+
+    #[test]
+    fn test_trace_reports_total_cycles() {
+        let mut cpu = cpu_with(vec![0xa5, 0x10, 0xbd, 0xff, 0x02, 0x00]);
+        cpu.register_x = 0x01;
+        cpu.step(); // LDA zp: 3 ciclos
+        cpu.step(); // LDA abs,X cruzando a página: 4 + 1
+
+        assert!(trace(&cpu, TraceVerbosity::Standard).ends_with(" SP:FD CYC:8"));
+    }
+
+    #[test]
+    fn test_filtered_trace_follows_nested_calls() {
+        // main: JSR sub1; BRK
+        // sub1 (8005): JSR sub2; RTS
+        // sub2 (8009): INX; RTS
+        let program = vec![0x20, 0x05, 0x80, 0x00, 0x00, 0x20, 0x09, 0x80, 0x60, 0xe8, 0x60];
+        let mut cpu = cpu_with(program.clone());
+
+        let calls = trace_filtered(&mut cpu, TraceVerbosity::Minimal, 100, |_, opcode| {
+            matches!(opcode.mnemonic, "JSR" | "RTS")
+        });
+        assert_eq!(calls, vec!["8000  JSR", "8005  JSR", "800A  RTS", "8008  RTS"]);
+        assert_eq!(cpu.register_x, 1);
+
+        let mut cpu = cpu_with(program);
+        let sub2 = trace_filtered(&mut cpu, TraceVerbosity::Minimal, 100, |pc, _| {
+            (0x8009..=0x800A).contains(&pc)
+        });
+        assert_eq!(sub2, vec!["8009  INX", "800A  RTS"]);
+    }
+
+    #[test]
+    fn test_symbols_name_breakpoints_and_operands() {
+        // main: JSR sub; STA score; BRK; sub: INX; RTS
+        let mut cpu = cpu_with(vec![0x20, 0x07, 0x80, 0x85, 0x10, 0x00, 0x00, 0xe8, 0x60]);
+        let symbols = [("main", 0x8000), ("sub", 0x8007), ("score", 0x0010)];
+        cpu.load_symbols(symbols.iter().map(|&(name, addr)| (name.to_string(), addr)).collect());
+
+        assert_eq!(cpu.break_at_symbol("sub"), Some(0x8007));
+        assert_eq!(cpu.break_at_symbol("missing"), None);
+        assert_eq!(cpu.breakpoints(), vec![0x8007]);
+
+        assert!(trace(&cpu, TraceVerbosity::Standard).starts_with("8000  20 07 80  JSR sub "));
+        assert_eq!(cpu.run_instructions(10), crate::cpu::RunExit::Breakpoint(0x8007));
+        assert_eq!(cpu.program_counter, 0x8007);
+        assert!(disassemble(&cpu, 0x8003, 0x8004).ends_with("STA score"));
+    }
+
+    #[test]
+    fn test_interrupt_log_lines() {
+        // LDA #$01; BRK; com a NMI chegando depois do LDA
+        let mut cpu = cpu_with(vec![0xa9, 0x01, 0x00]);
+        cpu.mem_write_u16(0xFFFA, 0x9000);
+        cpu.mem_write(0x9000, 0x40); // RTI
+        cpu.set_interrupt_logging(true);
+
+        cpu.step();
+        cpu.set_nmi_line(true);
+        cpu.run().unwrap();
+
+        let log: Vec<String> = cpu.take_events().iter().map(event_line).collect();
+        assert_eq!(
+            log,
+            vec![
+                "NMI  8002 -> vector FFFA CYC:2",
+                "BRK  8002 -> vector FFFE CYC:15",
+            ]
+        );
+    }
+}